@@ -1,12 +1,34 @@
-//! Whsper Stellar - Read-only query functions for pending claim information.
-//! Designed for UI/frontend integration. No authentication required.
+//! Whsper Stellar - Claim lifecycle contract and query functions for pending
+//! claim information. Mutating entrypoints (`create_claim`, `execute_claim`,
+//! `cancel_claim`) require the relevant party's auth; queries remain open for
+//! UI/frontend integration.
+//!
+//! ## Events
+//!
+//! Every claim state transition publishes a Soroban event so off-chain
+//! indexers can maintain a queryable view instead of polling the list
+//! queries. Topics are `(symbol_short!("claim"), symbol_short!(<kind>), recipient)`;
+//! the data payload is always `(id: u64, creator: Address, amount: i128, token: Address)`.
+//!
+//! | kind          | published by            |
+//! |---------------|--------------------------|
+//! | `"created"`   | `create_claim`           |
+//! | `"executed"`  | `execute_claim`          |
+//! | `"cancelled"` | `cancel_claim`, `sweep_expired_claims` (per swept claim) |
 
 #![cfg_attr(target_family = "wasm", no_std)]
 
+#[cfg(test)]
+extern crate std;
+
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, Symbol, Vec,
+    contract, contracterror, contractimpl, contracttype, symbol_short, token, Address, Env, Map,
+    Symbol, Vec,
 };
 
+#[cfg(test)]
+mod test;
+
 /// Maximum number of claims to return per page (pagination limit).
 pub const MAX_PAGE_SIZE: u32 = 100;
 
@@ -20,14 +42,77 @@ pub enum ClaimStatus {
     Cancelled = 2,
 }
 
+/// Which clock a new claim's window is anchored to. Ledger-sequence anchoring
+/// is deterministic and reorg-safe; time anchoring is wall-clock but can
+/// drift with ledger close times.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ExpirationMode {
+    #[default]
+    Time = 0,
+    Ledger = 1,
+}
+
+/// A point in time expressed either as a ledger timestamp, a ledger sequence
+/// number, or never. Mirrors cw0's `Expiration` so claim windows can be
+/// anchored to wall-clock time or block height, per deployment.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Expiration {
+    AtTime(u64),
+    AtLedger(u32),
+    Never,
+}
+
+impl Expiration {
+    /// True once the current ledger timestamp/sequence has reached this point.
+    /// `Never` is never expired.
+    pub fn is_expired(&self, env: &Env) -> bool {
+        match self {
+            Expiration::AtTime(t) => env.ledger().timestamp() >= *t,
+            Expiration::AtLedger(l) => env.ledger().sequence() >= *l,
+            Expiration::Never => false,
+        }
+    }
+
+    /// True once the current ledger timestamp/sequence is strictly past this
+    /// point. Unlike `is_expired` (`>=`), this treats the boundary itself as
+    /// still valid, for call sites that need an inclusive upper bound.
+    /// `Never` is never past.
+    fn is_past(&self, env: &Env) -> bool {
+        match self {
+            Expiration::AtTime(t) => env.ledger().timestamp() > *t,
+            Expiration::AtLedger(l) => env.ledger().sequence() > *l,
+            Expiration::Never => false,
+        }
+    }
+
+    /// Advance this point by `secs` (if `AtTime`) or `ledgers` (if `AtLedger`).
+    /// `Never` is unaffected.
+    fn advance(&self, secs: u64, ledgers: u32) -> Expiration {
+        match self {
+            Expiration::AtTime(t) => Expiration::AtTime(t + secs),
+            Expiration::AtLedger(l) => Expiration::AtLedger(l + ledgers),
+            Expiration::Never => Expiration::Never,
+        }
+    }
+}
+
 /// Configuration for claim window (when claims can be executed).
 #[contracttype]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct ClaimWindowConfig {
-    /// Duration in ledger seconds for the claim window.
+    /// Which clock new claims are anchored to.
+    pub mode: ExpirationMode,
+    /// Duration in ledger seconds for the claim window (used when `mode` is `Time`).
     pub window_duration_secs: u64,
-    /// Minimum time between claim window start and when a claim can be executed.
+    /// Duration in ledger sequence numbers for the claim window (used when `mode` is `Ledger`).
+    pub window_duration_ledgers: u32,
+    /// Minimum time between claim window start and when a claim can be executed (`Time` mode).
     pub min_claim_delay_secs: u64,
+    /// Minimum ledgers between claim window start and when a claim can be executed (`Ledger` mode).
+    pub min_claim_delay_ledgers: u32,
 }
 
 /// Pending claim data structure.
@@ -41,8 +126,21 @@ pub struct PendingClaim {
     pub token: Address,
     pub status: ClaimStatus,
     pub created_at: u64,
-    pub claim_window_start: u64,
-    pub claim_window_end: u64,
+    pub claim_window_start: Expiration,
+    pub claim_window_end: Expiration,
+}
+
+/// A single page of claims returned by the cursor-based list queries.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClaimPage {
+    pub items: Vec<PendingClaim>,
+    /// Id of the last index entry examined for this page (for
+    /// `get_claims_by_recipient`/`get_claims_by_creator`, the last claim
+    /// included; for `get_expired_claims`, possibly a non-matching claim).
+    /// Pass as `start_after` to fetch the next page; `None` means the index
+    /// vector is exhausted.
+    pub next_cursor: Option<u64>,
 }
 
 /// Result type for get_pending_claim - returns None for non-existent claims.
@@ -53,11 +151,30 @@ pub enum GetClaimResult {
     NotFound,
 }
 
+/// Errors returned by the mutating claim lifecycle entrypoints.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ClaimError {
+    /// No claim exists under the given id.
+    ClaimNotFound = 1,
+    /// The claim is not `Pending`, so it cannot transition.
+    ClaimNotPending = 2,
+    /// `execute_claim` was called outside `[claim_window_start + min_claim_delay, claim_window_end]`,
+    /// where the delay is `min_claim_delay_secs` (`Time` mode) or `min_claim_delay_ledgers` (`Ledger` mode).
+    OutsideClaimWindow = 3,
+    /// The caller is not the claim's recipient.
+    NotRecipient = 4,
+    /// The caller is not the claim's creator.
+    NotCreator = 5,
+}
+
 // Storage keys
 const CLAIM_KEY: Symbol = symbol_short!("claim");
 const CLAIMS_BY_RECIPIENT: Symbol = symbol_short!("claims_rc");
 const CLAIMS_BY_CREATOR: Symbol = symbol_short!("claims_cr");
 const CLAIM_WINDOW_CONFIG: Symbol = symbol_short!("claim_cfg");
+const NEXT_CLAIM_ID: Symbol = symbol_short!("next_id");
 
 #[contract]
 pub struct WhsperStellar;
@@ -73,18 +190,114 @@ impl WhsperStellar {
         }
     }
 
-    /// Get claims by recipient address with pagination.
+    /// Get claims by recipient address with cursor-based pagination.
     /// - `limit`: Max items to return (capped at MAX_PAGE_SIZE)
     /// - `include_claimed_cancelled`: If false, filters out Claimed and Cancelled claims.
+    /// - `start_after`: Last claim id seen by the caller; ids `<= start_after` are skipped.
+    /// - `only_expired`: If true, only returns claims whose `claim_window_end` has been reached
+    ///   (`Expiration::is_expired`).
     pub fn get_claims_by_recipient(
         env: Env,
         recipient: Address,
         limit: u32,
         include_claimed_cancelled: Option<bool>,
-    ) -> Vec<PendingClaim> {
+        start_after: Option<u64>,
+        only_expired: Option<bool>,
+    ) -> ClaimPage {
+        let key = (CLAIMS_BY_RECIPIENT, recipient);
+        Self::page_claim_ids(
+            &env,
+            key,
+            limit,
+            include_claimed_cancelled,
+            start_after,
+            only_expired,
+        )
+    }
+
+    /// Get claims by creator address with cursor-based pagination.
+    /// - `limit`: Max items to return (capped at MAX_PAGE_SIZE)
+    /// - `include_claimed_cancelled`: If false, filters out Claimed and Cancelled claims.
+    /// - `start_after`: Last claim id seen by the caller; ids `<= start_after` are skipped.
+    /// - `only_expired`: If true, only returns claims whose `claim_window_end` has been reached
+    ///   (`Expiration::is_expired`).
+    pub fn get_claims_by_creator(
+        env: Env,
+        creator: Address,
+        limit: u32,
+        include_claimed_cancelled: Option<bool>,
+        start_after: Option<u64>,
+        only_expired: Option<bool>,
+    ) -> ClaimPage {
+        let key = (CLAIMS_BY_CREATOR, creator);
+        Self::page_claim_ids(
+            &env,
+            key,
+            limit,
+            include_claimed_cancelled,
+            start_after,
+            only_expired,
+        )
+    }
+
+    /// Shared keyset pagination over an index vector of claim ids: skip ids
+    /// `<= start_after`, collect up to `limit` matching claims, and report the
+    /// last included id as `next_cursor` (or `None` once exhausted).
+    fn page_claim_ids(
+        env: &Env,
+        index_key: (Symbol, Address),
+        limit: u32,
+        include_claimed_cancelled: Option<bool>,
+        start_after: Option<u64>,
+        only_expired: Option<bool>,
+    ) -> ClaimPage {
         let limit = limit.min(MAX_PAGE_SIZE).max(1);
         let include_all = include_claimed_cancelled.unwrap_or(false);
+        let only_expired = only_expired.unwrap_or(false);
+
+        let claim_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&index_key)
+            .unwrap_or_else(|| Vec::new(env));
+
+        let mut items = Vec::new(env);
+        let mut last_seen: Option<u64> = None;
+        let mut exhausted = true;
+        for id in claim_ids.iter() {
+            if let Some(cursor) = start_after {
+                if id <= cursor {
+                    continue;
+                }
+            }
+            if items.len() >= limit {
+                exhausted = false;
+                break;
+            }
+            let claim_result = Self::get_pending_claim(env.clone(), id);
+            if let GetClaimResult::Found(claim) = claim_result {
+                let status_ok = include_all || claim.status == ClaimStatus::Pending;
+                let expiry_ok = !only_expired || claim.claim_window_end.is_expired(env);
+                if status_ok && expiry_ok {
+                    items.push_back(claim);
+                }
+            }
+            last_seen = Some(id);
+        }
+        let next_cursor = if exhausted { None } else { last_seen };
+        ClaimPage { items, next_cursor }
+    }
 
+    /// Total claimable value for `recipient`, grouped by token. Walks the
+    /// recipient's index vector once and accumulates `amount` per distinct
+    /// `token`, so dashboards don't have to page through every claim and sum
+    /// client-side.
+    /// - `only_pending`: If true, only sums `Pending` claims inside their live window.
+    pub fn get_claimable_totals(
+        env: Env,
+        recipient: Address,
+        only_pending: bool,
+    ) -> Vec<(Address, i128)> {
         let key = (CLAIMS_BY_RECIPIENT, recipient);
         let claim_ids: Vec<u64> = env
             .storage()
@@ -92,33 +305,218 @@ impl WhsperStellar {
             .get(&key)
             .unwrap_or_else(|| Vec::new(&env));
 
+        let mut totals: Map<Address, i128> = Map::new(&env);
+        for id in claim_ids.iter() {
+            let claim_result = Self::get_pending_claim(env.clone(), id);
+            if let GetClaimResult::Found(claim) = claim_result {
+                if only_pending
+                    && (claim.status != ClaimStatus::Pending
+                        || claim.claim_window_end.is_past(&env))
+                {
+                    continue;
+                }
+                let running = totals.get(claim.token.clone()).unwrap_or(0);
+                totals.set(claim.token, running + claim.amount);
+            }
+        }
+
         let mut result = Vec::new(&env);
+        for (token, total) in totals.iter() {
+            result.push_back((token, total));
+        }
+        result
+    }
+
+    /// Get the claim window configuration. Returns default config if not set.
+    pub fn get_claim_window_config(env: Env) -> ClaimWindowConfig {
+        env.storage()
+            .persistent()
+            .get(&CLAIM_WINDOW_CONFIG)
+            .unwrap_or_else(|| ClaimWindowConfig::default())
+    }
+
+    /// Create a new pending claim locking `amount` of `token` for `recipient`.
+    /// Requires `creator`'s auth and escrows `amount` of `token` from
+    /// `creator` into the contract, so `execute_claim`/`cancel_claim` always
+    /// have real funds to pay out. Allocates a monotonic claim id and derives
+    /// the claim window from the stored `ClaimWindowConfig`.
+    pub fn create_claim(
+        env: Env,
+        creator: Address,
+        recipient: Address,
+        amount: i128,
+        token: Address,
+    ) -> u64 {
+        creator.require_auth();
+
+        let id: u64 = env.storage().persistent().get(&NEXT_CLAIM_ID).unwrap_or(0);
+        env.storage().persistent().set(&NEXT_CLAIM_ID, &(id + 1));
+
+        let config = Self::get_claim_window_config(env.clone());
+        let now = env.ledger().timestamp();
+        let claim_window_start = match config.mode {
+            ExpirationMode::Time => Expiration::AtTime(now),
+            ExpirationMode::Ledger => Expiration::AtLedger(env.ledger().sequence()),
+        };
+        let claim_window_end =
+            claim_window_start.advance(config.window_duration_secs, config.window_duration_ledgers);
+
+        let claim = PendingClaim {
+            id,
+            creator: creator.clone(),
+            recipient: recipient.clone(),
+            amount,
+            token: token.clone(),
+            status: ClaimStatus::Pending,
+            created_at: now,
+            claim_window_start,
+            claim_window_end,
+        };
+        // Effects before interactions: persist the claim and its indexes
+        // before the external token transfer below, so a reentrant call
+        // observes a consistent, already-recorded claim.
+        env.storage().persistent().set(&(CLAIM_KEY, id), &claim);
+
+        Self::push_claim_id(&env, CLAIMS_BY_RECIPIENT, recipient, id);
+        Self::push_claim_id(&env, CLAIMS_BY_CREATOR, creator.clone(), id);
+
+        Self::emit_claim_event(&env, symbol_short!("created"), &claim);
+
+        let token_client = token::Client::new(&env, &token);
+        token_client.transfer(&creator, &env.current_contract_address(), &amount);
+
+        id
+    }
+
+    /// Execute a pending claim, transferring `amount` of `token` to `recipient`.
+    /// Requires `recipient`'s auth and that `now` falls within the claim window
+    /// (respecting `min_claim_delay_secs` or `min_claim_delay_ledgers`, per mode).
+    pub fn execute_claim(env: Env, recipient: Address, claim_id: u64) -> Result<(), ClaimError> {
+        recipient.require_auth();
+
+        let mut claim = Self::require_claim(&env, claim_id)?;
+        if claim.status != ClaimStatus::Pending {
+            return Err(ClaimError::ClaimNotPending);
+        }
+        if claim.recipient != recipient {
+            return Err(ClaimError::NotRecipient);
+        }
+
+        let config = Self::get_claim_window_config(env.clone());
+        let earliest = claim
+            .claim_window_start
+            .advance(config.min_claim_delay_secs, config.min_claim_delay_ledgers);
+        if !earliest.is_expired(&env) || claim.claim_window_end.is_past(&env) {
+            return Err(ClaimError::OutsideClaimWindow);
+        }
+
+        // Effects before interactions: flip status and persist before the
+        // external token transfer below, so a reentrant call sees the claim
+        // as already `Claimed` rather than still `Pending`.
+        claim.status = ClaimStatus::Claimed;
+        env.storage()
+            .persistent()
+            .set(&(CLAIM_KEY, claim_id), &claim);
+        Self::emit_claim_event(&env, symbol_short!("executed"), &claim);
+
+        let token_client = token::Client::new(&env, &claim.token);
+        token_client.transfer(
+            &env.current_contract_address(),
+            &claim.recipient,
+            &claim.amount,
+        );
+
+        Ok(())
+    }
+
+    /// Cancel a pending claim, returning the locked funds to `creator`.
+    /// Only the original creator may cancel, and only while `Pending`.
+    pub fn cancel_claim(env: Env, creator: Address, claim_id: u64) -> Result<(), ClaimError> {
+        creator.require_auth();
+
+        let mut claim = Self::require_claim(&env, claim_id)?;
+        if claim.status != ClaimStatus::Pending {
+            return Err(ClaimError::ClaimNotPending);
+        }
+        if claim.creator != creator {
+            return Err(ClaimError::NotCreator);
+        }
+
+        // Effects before interactions: flip status and persist before the
+        // external token transfer below, so a reentrant call sees the claim
+        // as already `Cancelled` rather than still `Pending`.
+        claim.status = ClaimStatus::Cancelled;
+        env.storage()
+            .persistent()
+            .set(&(CLAIM_KEY, claim_id), &claim);
+        Self::emit_claim_event(&env, symbol_short!("cancelled"), &claim);
+
+        let token_client = token::Client::new(&env, &claim.token);
+        token_client.transfer(
+            &env.current_contract_address(),
+            &claim.creator,
+            &claim.amount,
+        );
+
+        Ok(())
+    }
+
+    /// Sweep stale pending claims owned by `creator`, refunding the locked
+    /// `amount` of `token` back to them and transitioning each to
+    /// `Cancelled`. Examines at most `max_to_process` ids from
+    /// `CLAIMS_BY_CREATOR` per call (not just matches), so the call stays
+    /// within resource limits regardless of how many non-expired or already
+    /// resolved claims precede the expired ones. Returns the number swept.
+    pub fn sweep_expired_claims(env: Env, creator: Address, max_to_process: u32) -> u32 {
+        let key = (CLAIMS_BY_CREATOR, creator.clone());
+        let claim_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| Vec::new(&env));
+
+        let mut swept = 0u32;
+        let mut examined = 0u32;
         for id in claim_ids.iter() {
-            if result.len() >= limit {
+            if examined >= max_to_process {
                 break;
             }
+            examined += 1;
+
             let claim_result = Self::get_pending_claim(env.clone(), id);
-            if let GetClaimResult::Found(claim) = claim_result {
-                if include_all || claim.status == ClaimStatus::Pending {
-                    result.push_back(claim);
+            if let GetClaimResult::Found(mut claim) = claim_result {
+                if claim.status != ClaimStatus::Pending || !claim.claim_window_end.is_past(&env) {
+                    continue;
                 }
+
+                // Effects before interactions: flip status and persist
+                // before the external token transfer below.
+                claim.status = ClaimStatus::Cancelled;
+                env.storage().persistent().set(&(CLAIM_KEY, id), &claim);
+                Self::emit_claim_event(&env, symbol_short!("cancelled"), &claim);
+
+                let token_client = token::Client::new(&env, &claim.token);
+                token_client.transfer(&env.current_contract_address(), &creator, &claim.amount);
+
+                swept += 1;
             }
         }
-        result
+        swept
     }
 
-    /// Get claims by creator address with pagination.
-    /// - `limit`: Max items to return (capped at MAX_PAGE_SIZE)
-    /// - `include_claimed_cancelled`: If false, filters out Claimed and Cancelled claims.
-    pub fn get_claims_by_creator(
+    /// Read-only discovery for keeper bots: claims owned by `creator` that
+    /// are still `Pending` but whose window has already closed, i.e. what
+    /// `sweep_expired_claims` would act on. Cursor-paginated like
+    /// `get_claims_by_recipient`/`get_claims_by_creator`: examines at most
+    /// `limit` ids (not just matches) and returns a `next_cursor` so a keeper
+    /// can resume a scan that didn't fit in one call.
+    pub fn get_expired_claims(
         env: Env,
         creator: Address,
         limit: u32,
-        include_claimed_cancelled: Option<bool>,
-    ) -> Vec<PendingClaim> {
+        start_after: Option<u64>,
+    ) -> ClaimPage {
         let limit = limit.min(MAX_PAGE_SIZE).max(1);
-        let include_all = include_claimed_cancelled.unwrap_or(false);
-
         let key = (CLAIMS_BY_CREATOR, creator);
         let claim_ids: Vec<u64> = env
             .storage()
@@ -126,29 +524,65 @@ impl WhsperStellar {
             .get(&key)
             .unwrap_or_else(|| Vec::new(&env));
 
-        let mut result = Vec::new(&env);
-        let len = claim_ids.len();
-        for i in 0..len {
-            if result.len() >= limit {
+        let mut items = Vec::new(&env);
+        let mut last_seen: Option<u64> = None;
+        let mut exhausted = true;
+        let mut examined = 0u32;
+        for id in claim_ids.iter() {
+            if let Some(cursor) = start_after {
+                if id <= cursor {
+                    continue;
+                }
+            }
+            if examined >= limit {
+                exhausted = false;
                 break;
             }
-            if let Some(id) = claim_ids.get(i) {
-                let claim_result = Self::get_pending_claim(env.clone(), id);
-                if let GetClaimResult::Found(claim) = claim_result {
-                    if include_all || claim.status == ClaimStatus::Pending {
-                        result.push_back(claim);
-                    }
+            examined += 1;
+
+            let claim_result = Self::get_pending_claim(env.clone(), id);
+            if let GetClaimResult::Found(claim) = claim_result {
+                if claim.status == ClaimStatus::Pending && claim.claim_window_end.is_past(&env) {
+                    items.push_back(claim);
                 }
             }
+            last_seen = Some(id);
         }
-        result
+        let next_cursor = if exhausted { None } else { last_seen };
+        ClaimPage { items, next_cursor }
     }
 
-    /// Get the claim window configuration. Returns default config if not set.
-    pub fn get_claim_window_config(env: Env) -> ClaimWindowConfig {
-        env.storage()
+    /// Fetch a claim or fail with `ClaimNotFound`.
+    fn require_claim(env: &Env, claim_id: u64) -> Result<PendingClaim, ClaimError> {
+        match Self::get_pending_claim(env.clone(), claim_id) {
+            GetClaimResult::Found(claim) => Ok(claim),
+            GetClaimResult::NotFound => Err(ClaimError::ClaimNotFound),
+        }
+    }
+
+    /// Publish a `(symbol_short!("claim"), symbol_short!(kind), recipient)`
+    /// event with data `(id, creator, amount, token)`. See the module-level
+    /// event schema table for the `kind` values emitted by each entrypoint.
+    fn emit_claim_event(env: &Env, kind: Symbol, claim: &PendingClaim) {
+        let topics = (CLAIM_KEY, kind, claim.recipient.clone());
+        let data = (
+            claim.id,
+            claim.creator.clone(),
+            claim.amount,
+            claim.token.clone(),
+        );
+        env.events().publish(topics, data);
+    }
+
+    /// Append `id` to the index vector stored under `(index_key, address)`.
+    fn push_claim_id(env: &Env, index_key: Symbol, address: Address, id: u64) {
+        let key = (index_key, address);
+        let mut ids: Vec<u64> = env
+            .storage()
             .persistent()
-            .get(&CLAIM_WINDOW_CONFIG)
-            .unwrap_or_else(|| ClaimWindowConfig::default())
+            .get(&key)
+            .unwrap_or_else(|| Vec::new(env));
+        ids.push_back(id);
+        env.storage().persistent().set(&key, &ids);
     }
 }
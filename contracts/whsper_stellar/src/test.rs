@@ -0,0 +1,470 @@
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events as _, Ledger as _};
+use soroban_sdk::{IntoVal, Val};
+use std::collections::HashMap;
+
+/// True if `kind` was published for `claim` by the contract, matching the
+/// topics/data shape built in `WhsperStellar::emit_claim_event`.
+fn has_claim_event(env: &Env, contract_id: &Address, kind: &str, claim: &PendingClaim) -> bool {
+    let expected_topics: Vec<Val> =
+        (CLAIM_KEY, Symbol::new(env, kind), claim.recipient.clone()).into_val(env);
+    let expected_data: Val = (
+        claim.id,
+        claim.creator.clone(),
+        claim.amount,
+        claim.token.clone(),
+    )
+        .into_val(env);
+
+    env.events()
+        .all()
+        .iter()
+        .any(|e| e.0 == *contract_id && e.1 == expected_topics && e.2 == expected_data)
+}
+
+struct TestCtx {
+    env: Env,
+    client: WhsperStellarClient<'static>,
+    creator: Address,
+    recipient: Address,
+    token_address: Address,
+    token_client: token::Client<'static>,
+}
+
+fn setup() -> TestCtx {
+    setup_with_config(ClaimWindowConfig {
+        mode: ExpirationMode::Time,
+        window_duration_secs: 100,
+        window_duration_ledgers: 0,
+        min_claim_delay_secs: 10,
+        min_claim_delay_ledgers: 0,
+    })
+}
+
+fn setup_with_config(config: ClaimWindowConfig) -> TestCtx {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register_contract(None, WhsperStellar);
+    let client = WhsperStellarClient::new(&env, &contract_id);
+
+    let token_admin = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(token_admin);
+    let token_client = token::Client::new(&env, &token_address);
+    let token_sac = token::StellarAssetClient::new(&env, &token_address);
+
+    let creator = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token_sac.mint(&creator, &1_000);
+
+    env.as_contract(&contract_id, || {
+        env.storage()
+            .persistent()
+            .set(&CLAIM_WINDOW_CONFIG, &config);
+    });
+
+    TestCtx {
+        env,
+        client,
+        creator,
+        recipient,
+        token_address,
+        token_client,
+    }
+}
+
+#[test]
+fn create_claim_escrows_funds_from_creator() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+
+    assert_eq!(id, 0);
+    assert_eq!(ctx.token_client.balance(&ctx.creator), 600);
+    assert_eq!(ctx.token_client.balance(&ctx.client.address), 400);
+}
+
+#[test]
+fn execute_claim_pays_recipient_from_escrow() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+
+    ctx.env.ledger().with_mut(|li| li.timestamp += 10);
+    ctx.client.execute_claim(&ctx.recipient, &id);
+
+    assert_eq!(ctx.token_client.balance(&ctx.recipient), 400);
+    assert_eq!(ctx.token_client.balance(&ctx.client.address), 0);
+    match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Claimed),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn execute_claim_at_exactly_window_end_succeeds() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+
+    ctx.env.ledger().with_mut(|li| li.timestamp += 100);
+    ctx.client.execute_claim(&ctx.recipient, &id);
+
+    match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Claimed),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn execute_claim_after_window_end_fails() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+    let result = ctx.client.try_execute_claim(&ctx.recipient, &id);
+
+    assert_eq!(result, Ok(Err(ClaimError::OutsideClaimWindow)));
+}
+
+#[test]
+fn cancel_claim_refunds_creator() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+    ctx.client.cancel_claim(&ctx.creator, &id);
+
+    assert_eq!(ctx.token_client.balance(&ctx.creator), 1_000);
+    assert_eq!(ctx.token_client.balance(&ctx.client.address), 0);
+    match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Cancelled),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn execute_claim_twice_is_rejected() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 10);
+    ctx.client.execute_claim(&ctx.recipient, &id);
+
+    let result = ctx.client.try_execute_claim(&ctx.recipient, &id);
+    assert_eq!(result, Ok(Err(ClaimError::ClaimNotPending)));
+}
+
+#[test]
+fn first_page_includes_claim_id_zero() {
+    let ctx = setup();
+
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+
+    let page = ctx
+        .client
+        .get_claims_by_recipient(&ctx.recipient, &1, &Some(true), &None, &None);
+
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items.get(0).unwrap().id, 0);
+    assert_eq!(page.next_cursor, Some(0));
+}
+
+#[test]
+fn next_cursor_resumes_after_claim_id_zero() {
+    let ctx = setup();
+
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+
+    let first = ctx
+        .client
+        .get_claims_by_recipient(&ctx.recipient, &1, &Some(true), &None, &None);
+    let second = ctx.client.get_claims_by_recipient(
+        &ctx.recipient,
+        &1,
+        &Some(true),
+        &first.next_cursor,
+        &None,
+    );
+
+    assert_eq!(second.items.len(), 1);
+    assert_eq!(second.items.get(0).unwrap().id, 1);
+    assert_eq!(second.next_cursor, None);
+}
+
+#[test]
+fn ledger_mode_windows_track_sequence_not_time() {
+    let ctx = setup_with_config(ClaimWindowConfig {
+        mode: ExpirationMode::Ledger,
+        window_duration_secs: 0,
+        window_duration_ledgers: 50,
+        min_claim_delay_secs: 0,
+        min_claim_delay_ledgers: 5,
+    });
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+
+    // Advancing wall-clock time alone must not open the window in Ledger mode.
+    ctx.env.ledger().with_mut(|li| li.timestamp += 1_000_000);
+    let too_early = ctx.client.try_execute_claim(&ctx.recipient, &id);
+    assert_eq!(too_early, Ok(Err(ClaimError::OutsideClaimWindow)));
+
+    ctx.env.ledger().with_mut(|li| li.sequence_number += 5);
+    ctx.client.execute_claim(&ctx.recipient, &id);
+
+    match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => {
+            assert_eq!(claim.status, ClaimStatus::Claimed);
+            assert!(matches!(claim.claim_window_end, Expiration::AtLedger(_)));
+        }
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn claimable_totals_are_grouped_by_token() {
+    let ctx = setup();
+
+    let other_token_admin = Address::generate(&ctx.env);
+    let other_token_address = ctx.env.register_stellar_asset_contract(other_token_admin);
+    let other_token_sac = token::StellarAssetClient::new(&ctx.env, &other_token_address);
+    other_token_sac.mint(&ctx.creator, &1_000);
+
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &300, &ctx.token_address);
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &150, &ctx.token_address);
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &250, &other_token_address);
+
+    let totals = ctx.client.get_claimable_totals(&ctx.recipient, &true);
+
+    let by_token: HashMap<Address, i128> = totals.iter().collect();
+    assert_eq!(by_token.len(), 2);
+    assert_eq!(by_token.get(&ctx.token_address), Some(&450));
+    assert_eq!(by_token.get(&other_token_address), Some(&250));
+}
+
+#[test]
+fn only_expired_filter_excludes_live_claims() {
+    let ctx = setup();
+
+    let expired_id =
+        ctx.client
+            .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+
+    let live_id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    let _ = live_id;
+
+    let page =
+        ctx.client
+            .get_claims_by_recipient(&ctx.recipient, &10, &Some(true), &None, &Some(true));
+
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items.get(0).unwrap().id, expired_id);
+}
+
+#[test]
+fn sweep_expired_claims_refunds_and_cancels() {
+    let ctx = setup();
+
+    let expired_id =
+        ctx.client
+            .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+
+    let live_id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+
+    let swept = ctx.client.sweep_expired_claims(&ctx.creator, &10);
+
+    assert_eq!(swept, 1);
+    assert_eq!(ctx.token_client.balance(&ctx.creator), 900);
+    assert_eq!(ctx.token_client.balance(&ctx.client.address), 100);
+    match ctx.client.get_pending_claim(&expired_id) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Cancelled),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+    match ctx.client.get_pending_claim(&live_id) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Pending),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn sweep_expired_claims_stops_at_max_to_process() {
+    let ctx = setup();
+
+    for _ in 0..3 {
+        ctx.client
+            .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    }
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+
+    let swept = ctx.client.sweep_expired_claims(&ctx.creator, &2);
+
+    assert_eq!(swept, 2);
+    match ctx.client.get_pending_claim(&2) {
+        GetClaimResult::Found(claim) => assert_eq!(claim.status, ClaimStatus::Pending),
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    }
+}
+
+#[test]
+fn get_expired_claims_matches_what_sweep_would_act_on() {
+    let ctx = setup();
+
+    let expired_id =
+        ctx.client
+            .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+
+    let page = ctx.client.get_expired_claims(&ctx.creator, &10, &None);
+
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items.get(0).unwrap().id, expired_id);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn get_expired_claims_cursor_resumes_scan_past_non_matching_ids() {
+    let ctx = setup();
+
+    let expired_id =
+        ctx.client
+            .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+
+    ctx.client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+
+    // Bound the scan to a single id so it stops right after the expired claim,
+    // leaving the live claim unexamined and the cursor non-exhausted.
+    let page = ctx.client.get_expired_claims(&ctx.creator, &1, &None);
+
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items.get(0).unwrap().id, expired_id);
+    assert_eq!(page.next_cursor, Some(expired_id));
+
+    let next_page = ctx
+        .client
+        .get_expired_claims(&ctx.creator, &1, &page.next_cursor);
+
+    assert_eq!(next_page.items.len(), 0);
+    assert_eq!(next_page.next_cursor, None);
+}
+
+#[test]
+fn create_claim_emits_created_event() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+    let claim = match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => claim,
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    };
+
+    assert!(has_claim_event(
+        &ctx.env,
+        &ctx.client.address,
+        "created",
+        &claim
+    ));
+}
+
+#[test]
+fn execute_claim_emits_executed_event() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 10);
+    ctx.client.execute_claim(&ctx.recipient, &id);
+
+    let claim = match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => claim,
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    };
+
+    assert!(has_claim_event(
+        &ctx.env,
+        &ctx.client.address,
+        "executed",
+        &claim
+    ));
+}
+
+#[test]
+fn cancel_claim_emits_cancelled_event() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &400, &ctx.token_address);
+    ctx.client.cancel_claim(&ctx.creator, &id);
+
+    let claim = match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => claim,
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    };
+
+    assert!(has_claim_event(
+        &ctx.env,
+        &ctx.client.address,
+        "cancelled",
+        &claim
+    ));
+}
+
+#[test]
+fn sweep_expired_claims_emits_cancelled_event() {
+    let ctx = setup();
+
+    let id = ctx
+        .client
+        .create_claim(&ctx.creator, &ctx.recipient, &100, &ctx.token_address);
+    ctx.env.ledger().with_mut(|li| li.timestamp += 101);
+    ctx.client.sweep_expired_claims(&ctx.creator, &10);
+
+    let claim = match ctx.client.get_pending_claim(&id) {
+        GetClaimResult::Found(claim) => claim,
+        GetClaimResult::NotFound => panic!("claim should exist"),
+    };
+
+    assert!(has_claim_event(
+        &ctx.env,
+        &ctx.client.address,
+        "cancelled",
+        &claim
+    ));
+}